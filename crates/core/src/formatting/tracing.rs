@@ -1,6 +1,7 @@
 use super::id::IdCounter;
 use super::*;
 use serde::Serialize;
+use std::collections::HashMap;
 use std::collections::HashSet;
 
 thread_local! {
@@ -80,6 +81,81 @@ pub struct TraceCondition {
   pub dependent_infos: Option<Vec<usize>>,
 }
 
+/// A single [Chrome Trace Event Format](https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU)
+/// complete ("X") event. Load an array of these into `chrome://tracing` or Perfetto to visually
+/// profile the IR-printing timeline.
+#[derive(Serialize)]
+pub struct ChromeTraceEvent {
+  pub name: String,
+  pub ph: &'static str,
+  /// Start time in microseconds.
+  pub ts: f64,
+  /// Duration in microseconds.
+  pub dur: f64,
+  pub pid: usize,
+  pub tid: usize,
+  pub args: ChromeTraceArgs,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChromeTraceArgs {
+  pub kind: &'static str,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub name: Option<String>,
+  pub print_node_id: usize,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub writer_node_id: Option<usize>,
+}
+
+impl TracePrintItem {
+  /// The print item's kind tag and, where it has one, its name — surfaced into the event args.
+  fn kind_and_name(&self) -> (&'static str, Option<String>) {
+    match self {
+      TracePrintItem::String(text) => ("string", Some(text.clone())),
+      TracePrintItem::Condition(condition) => ("condition", Some(condition.name.clone())),
+      TracePrintItem::Info(info) => ("info", Some(info.name.clone())),
+      TracePrintItem::Signal(_) => ("signal", None),
+      TracePrintItem::RcPath(_) => ("rcPath", None),
+    }
+  }
+}
+
+impl TracingResult {
+  /// Converts the captured traces into Chrome Trace Event Format duration events.
+  ///
+  /// Each `Trace` becomes one complete ("X") event whose duration is the gap to the next trace's
+  /// `nanos` (zero for the final trace), with the print/writer node ids and the print item's kind
+  /// and name recorded in `args`. All events are emitted on a single track.
+  pub fn to_chrome_trace_events(&self) -> Vec<ChromeTraceEvent> {
+    let print_nodes: HashMap<usize, &TracePrintNode> = self.print_nodes.iter().map(|node| (node.print_node_id, node)).collect();
+
+    let mut events = Vec::with_capacity(self.traces.len());
+    for (i, trace) in self.traces.iter().enumerate() {
+      let end_nanos = self.traces.get(i + 1).map(|next| next.nanos).unwrap_or(trace.nanos);
+      let (kind, name) = print_nodes
+        .get(&trace.print_node_id)
+        .map(|node| node.print_item.kind_and_name())
+        .unwrap_or(("unknown", None));
+      events.push(ChromeTraceEvent {
+        name: name.clone().unwrap_or_else(|| kind.to_string()),
+        ph: "X",
+        ts: trace.nanos as f64 / 1_000.0,
+        dur: (end_nanos - trace.nanos) as f64 / 1_000.0,
+        pid: 0,
+        tid: 0,
+        args: ChromeTraceArgs {
+          kind,
+          name,
+          print_node_id: trace.print_node_id,
+          writer_node_id: trace.writer_node_id,
+        },
+      });
+    }
+    events
+  }
+}
+
 /// Gets all the TracePrintNodes for analysis from the starting node.
 pub fn get_trace_print_nodes(start_node: Option<PrintItemPath>) -> Vec<TracePrintNode> {
   let mut print_nodes = Vec::new();
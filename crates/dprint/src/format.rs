@@ -1,11 +1,18 @@
+use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::Result;
 use dprint_core::configuration::ConfigKeyMap;
+use dprint_core::configuration::ConfigKeyValue;
+use serde::Serialize;
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::ops::Range;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 use std::time::Instant;
 
 use crate::environment::Environment;
@@ -14,10 +21,22 @@ use crate::paths::PluginNames;
 use crate::plugins::do_batch_format;
 use crate::plugins::PluginAndPoolMutRef;
 use crate::plugins::PluginPools;
+use crate::plugins::Severity;
 use crate::plugins::TakePluginResult;
 use crate::utils::ErrorCountLogger;
 use crate::utils::FileText;
 
+/// How long to wait for a filesystem burst to settle before re-formatting in watch mode.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Stream of changed paths produced by an `Environment`'s filesystem watcher.
+pub trait FsChangeReceiver {
+  /// Blocks until the next changed path, returning `None` once the watcher is closed.
+  fn recv(&self) -> Option<PathBuf>;
+  /// Returns the next changed path if one arrives within `timeout`, otherwise `None`.
+  fn recv_timeout(&self, timeout: Duration) -> Option<PathBuf>;
+}
+
 pub fn format_with_plugin_pools<'a, TEnvironment: Environment>(
   file_name: &Path,
   file_text: &'a str,
@@ -25,19 +44,30 @@ pub fn format_with_plugin_pools<'a, TEnvironment: Environment>(
   plugin_pools: &Arc<PluginPools<TEnvironment>>,
 ) -> Result<Cow<'a, str>> {
   let plugin_names = plugin_pools.get_plugin_names_from_file_name(file_name);
+  let config_override = extract_config_overrides(environment, file_text);
   let mut file_text = Cow::Borrowed(file_text);
   for plugin_name in plugin_names {
     let plugin_pool = plugin_pools.get_pool(&plugin_name).unwrap();
     let error_logger = ErrorCountLogger::from_environment(environment);
     match plugin_pool.take_or_create_checking_config_diagnostics(&error_logger)? {
       TakePluginResult::Success(mut initialized_plugin) => {
-        let result = initialized_plugin.format_text(file_name, &file_text, &ConfigKeyMap::new());
+        let result = initialized_plugin.format_text(file_name, &file_text, &config_override);
         plugin_pool.release(initialized_plugin);
         file_text = Cow::Owned(result?); // release plugin above, then propagate this error
       }
-      TakePluginResult::HadDiagnostics => {
+      TakePluginResult::HadDiagnostics(Severity::Error) => {
         bail!("Had {} configuration errors.", error_logger.get_error_count());
       }
+      TakePluginResult::HadDiagnostics(Severity::Warning) => {
+        // recoverable: surface the warnings (visibly, not just under --verbose) and skip this
+        // plugin rather than aborting the run
+        error_logger.log_warn(&format!(
+          "Skipping {} for {} due to {} configuration warning(s).",
+          plugin_name,
+          file_name.display(),
+          error_logger.get_warning_count(),
+        ));
+      }
     }
   }
   Ok(file_text)
@@ -48,6 +78,7 @@ pub fn run_parallelized<F, TEnvironment: Environment>(
   environment: &TEnvironment,
   plugin_pools: Arc<PluginPools<TEnvironment>>,
   incremental_file: Option<Arc<IncrementalFile<TEnvironment>>>,
+  cancel_token: Arc<AtomicBool>,
   f: F,
 ) -> Result<()>
 where
@@ -55,75 +86,549 @@ where
 {
   let error_logger = ErrorCountLogger::from_environment(environment);
 
-  do_batch_format(environment, &error_logger, &plugin_pools, file_paths_by_plugins, {
+  do_format_round(file_paths_by_plugins, environment, &error_logger, &plugin_pools, &incremental_file, &cancel_token, &f)?;
+
+  // distinguish "had errors" from "had only warnings": only errors abort the run, while warnings
+  // are reported and let formatting finish. Warning-severity config diagnostics from the batch's
+  // per-plugin check are recorded into this same `error_logger`, so `get_warning_count()` below
+  // reflects them on the `dprint fmt` path too.
+  let error_count = error_logger.get_error_count();
+  if error_count > 0 {
+    bail!("Had {0} error(s) formatting.", error_count)
+  }
+  let warning_count = error_logger.get_warning_count();
+  if warning_count > 0 {
+    error_logger.log_warn(&format!("Finished with {0} warning(s).", warning_count));
+  }
+  Ok(())
+}
+
+/// Like [`run_parallelized`], but surfaces the minimal set of [`TextEdit`]s between each file's
+/// original and formatted text so editors can apply incremental changes.
+pub fn run_parallelized_edits<F, TEnvironment: Environment>(
+  file_paths_by_plugins: HashMap<PluginNames, Vec<PathBuf>>,
+  environment: &TEnvironment,
+  plugin_pools: Arc<PluginPools<TEnvironment>>,
+  incremental_file: Option<Arc<IncrementalFile<TEnvironment>>>,
+  cancel_token: Arc<AtomicBool>,
+  f: F,
+) -> Result<()>
+where
+  F: Fn(&Path, Vec<TextEdit>, bool, Instant, &TEnvironment) -> Result<()> + Send + 'static + Clone,
+{
+  let error_logger = ErrorCountLogger::from_environment(environment);
+
+  // hand the same cancel token to do_batch_format (which passes it to every Worker::new) and to
+  // the per-file closure, so the worker-side flag and the between-plugins check are one Arc
+  do_batch_format(environment, &error_logger, &plugin_pools, file_paths_by_plugins, cancel_token.clone(), {
     let environment = environment.clone();
     let error_logger = error_logger.clone();
     move |plugins, file_path| {
-      let result = run_for_file_path(&environment, &incremental_file, plugins, file_path, f.clone());
+      let result = run_for_file_path_edits(&environment, &incremental_file, &cancel_token, plugins, file_path, f.clone());
       if let Err(err) = result {
         error_logger.log_error(&format!("Error formatting {}. Message: {}", file_path.display(), err));
       }
     }
   })?;
 
+  // same gate as run_parallelized: only errors abort, warnings are reported and let formatting finish
   let error_count = error_logger.get_error_count();
-  return if error_count == 0 {
-    Ok(())
-  } else {
+  if error_count > 0 {
     bail!("Had {0} error(s) formatting.", error_count)
+  }
+  let warning_count = error_logger.get_warning_count();
+  if warning_count > 0 {
+    error_logger.log_warn(&format!("Finished with {0} warning(s).", warning_count));
+  }
+  Ok(())
+}
+
+/// Keeps the warmed `PluginPools` (and therefore the work-stealing `Worker`s) alive and
+/// re-formats files as they change on disk, so editors and `dprint fmt --watch` don't pay
+/// plugin-initialization cost on every run.
+pub fn run_watch<F, TEnvironment: Environment>(
+  file_paths_by_plugins: HashMap<PluginNames, Vec<PathBuf>>,
+  environment: &TEnvironment,
+  plugin_pools: Arc<PluginPools<TEnvironment>>,
+  incremental_file: Option<Arc<IncrementalFile<TEnvironment>>>,
+  cancel_token: Arc<AtomicBool>,
+  f: F,
+) -> Result<()>
+where
+  F: Fn(&Path, &str, String, bool, Instant, &TEnvironment) -> Result<()> + Send + 'static + Clone,
+{
+  let error_logger = ErrorCountLogger::from_environment(environment);
+
+  // format everything once up front using the freshly warmed pools
+  do_format_round(file_paths_by_plugins, environment, &error_logger, &plugin_pools, &incremental_file, &cancel_token, &f)?;
+
+  // then stay resident, only re-bucketing and re-formatting the paths that actually change
+  let watcher = environment.watch_changes()?;
+  loop {
+    let changed_paths = match watcher.recv() {
+      Some(path) => debounce(&watcher, path),
+      None => return Ok(()), // watcher closed
+    };
+    let file_paths_by_plugins = bucket_by_plugins(environment, &plugin_pools, changed_paths);
+    if file_paths_by_plugins.is_empty() {
+      continue; // nothing we handle changed (or the changed paths no longer exist)
+    }
+    // reuse the warmed pools for another round; the IncrementalFile check in run_for_file_path
+    // short-circuits saves that don't alter formatting
+    do_format_round(file_paths_by_plugins, environment, &error_logger, &plugin_pools, &incremental_file, &cancel_token, &f)?;
+  }
+}
+
+/// Formats a single batch over the given pools, logging per-file errors through `error_logger`.
+fn do_format_round<F, TEnvironment: Environment>(
+  file_paths_by_plugins: HashMap<PluginNames, Vec<PathBuf>>,
+  environment: &TEnvironment,
+  error_logger: &ErrorCountLogger<TEnvironment>,
+  plugin_pools: &Arc<PluginPools<TEnvironment>>,
+  incremental_file: &Option<Arc<IncrementalFile<TEnvironment>>>,
+  cancel_token: &Arc<AtomicBool>,
+  f: &F,
+) -> Result<()>
+where
+  F: Fn(&Path, &str, String, bool, Instant, &TEnvironment) -> Result<()> + Send + 'static + Clone,
+{
+  // hand the same cancel token to do_batch_format (which passes it to every Worker::new) and to
+  // the per-file closure, so the worker-side flag and the between-plugins check are one Arc
+  do_batch_format(environment, error_logger, plugin_pools, file_paths_by_plugins, cancel_token.clone(), {
+    let environment = environment.clone();
+    let error_logger = error_logger.clone();
+    let incremental_file = incremental_file.clone();
+    let cancel_token = cancel_token.clone();
+    let f = f.clone();
+    move |plugins, file_path| {
+      let result = run_for_file_path(&environment, &incremental_file, &cancel_token, plugins, file_path, f.clone());
+      if let Err(err) = result {
+        error_logger.log_error(&format!("Error formatting {}. Message: {}", file_path.display(), err));
+      }
+    }
+  })
+}
+
+/// Drains the rest of a filesystem burst, extending the window while events keep arriving
+/// within `WATCH_DEBOUNCE`, and returns the de-duplicated set of changed paths.
+fn debounce<TWatcher: FsChangeReceiver>(watcher: &TWatcher, first: PathBuf) -> Vec<PathBuf> {
+  let mut changed = vec![first];
+  while let Some(path) = watcher.recv_timeout(WATCH_DEBOUNCE) {
+    if !changed.contains(&path) {
+      changed.push(path);
+    }
+  }
+  changed
+}
+
+/// Re-buckets changed paths into the `HashMap<PluginNames, Vec<PathBuf>>` shape that
+/// `do_batch_format` expects, dropping any path no plugin handles as well as paths that no longer
+/// exist (e.g. a file deleted or renamed in the burst) so watch mode doesn't emit spurious
+/// "Error formatting" messages for them.
+fn bucket_by_plugins<TEnvironment: Environment>(
+  environment: &TEnvironment,
+  plugin_pools: &Arc<PluginPools<TEnvironment>>,
+  paths: Vec<PathBuf>,
+) -> HashMap<PluginNames, Vec<PathBuf>> {
+  let mut file_paths_by_plugins: HashMap<PluginNames, Vec<PathBuf>> = HashMap::new();
+  for path in paths {
+    if !environment.path_exists(&path) {
+      continue;
+    }
+    let plugin_names: PluginNames = plugin_pools.get_plugin_names_from_file_name(&path).collect();
+    if plugin_names.is_empty() {
+      continue;
+    }
+    file_paths_by_plugins.entry(plugin_names).or_default().push(path);
+  }
+  file_paths_by_plugins
+}
+
+#[inline]
+fn run_for_file_path<F, TEnvironment: Environment>(
+  environment: &TEnvironment,
+  incremental_file: &Option<Arc<IncrementalFile<TEnvironment>>>,
+  cancel_token: &Arc<AtomicBool>,
+  plugins: Vec<PluginAndPoolMutRef<TEnvironment>>,
+  file_path: &Path,
+  f: F,
+) -> Result<()>
+where
+  F: Fn(&Path, &str, String, bool, Instant, &TEnvironment) -> Result<()> + Send + 'static + Clone,
+{
+  let (file_text, formatted) = match format_file_path(environment, incremental_file, cancel_token, plugins, file_path)? {
+    Some(formatted) => formatted,
+    None => return Ok(()),
+  };
+
+  f(file_path, file_text.as_str(), formatted.text, file_text.has_bom(), formatted.start_instant, environment)?;
+
+  Ok(())
+}
+
+/// Variant of [`run_for_file_path`] that surfaces the minimal set of edits between the original
+/// file text and the formatted result instead of the whole formatted string, so an LSP server can
+/// apply incremental changes without rewriting unchanged regions.
+#[inline]
+fn run_for_file_path_edits<F, TEnvironment: Environment>(
+  environment: &TEnvironment,
+  incremental_file: &Option<Arc<IncrementalFile<TEnvironment>>>,
+  cancel_token: &Arc<AtomicBool>,
+  plugins: Vec<PluginAndPoolMutRef<TEnvironment>>,
+  file_path: &Path,
+  f: F,
+) -> Result<()>
+where
+  F: Fn(&Path, Vec<TextEdit>, bool, Instant, &TEnvironment) -> Result<()> + Send + 'static + Clone,
+{
+  let (file_text, formatted) = match format_file_path(environment, incremental_file, cancel_token, plugins, file_path)? {
+    Some(formatted) => formatted,
+    None => return Ok(()),
+  };
+
+  let edits = compute_text_edits(file_text.as_str(), &formatted.text);
+  f(file_path, edits, file_text.has_bom(), formatted.start_instant, environment)?;
+
+  Ok(())
+}
+
+struct FormattedFile {
+  text: String,
+  start_instant: Instant,
+}
+
+/// Reads and formats a single file through its plugins, returning the formatted text alongside the
+/// original `FileText`. Returns `None` when the incremental cache reports no change or when the
+/// cancel token is set before a plugin runs.
+#[inline]
+fn format_file_path<TEnvironment: Environment>(
+  environment: &TEnvironment,
+  incremental_file: &Option<Arc<IncrementalFile<TEnvironment>>>,
+  cancel_token: &Arc<AtomicBool>,
+  mut plugins: Vec<PluginAndPoolMutRef<TEnvironment>>,
+  file_path: &Path,
+) -> Result<Option<(FileText, FormattedFile)>> {
+  let file_text = FileText::new(environment.read_file(&file_path)?);
+
+  if let Some(incremental_file) = incremental_file {
+    if incremental_file.is_file_same(file_path, file_text.as_str()) {
+      log_verbose!(environment, "No change: {}", file_path.display());
+      return Ok(None);
+    }
+  }
+
+  let config_override = extract_config_overrides(environment, file_text.as_str());
+
+  let (start_instant, formatted_text) = {
+    let start_instant = Instant::now();
+    let mut file_text = Cow::Borrowed(file_text.as_str());
+    let plugins_len = plugins.len();
+    for (i, plugin) in plugins.iter_mut().enumerate() {
+      // stop between plugins once the pool is cancelled so we don't format further; unwind cleanly
+      // via the cancelled sentinel rather than an error so an abort doesn't fail the run
+      if cancel_token.load(Ordering::Relaxed) {
+        log_verbose!(environment, "Cancelled: {}", file_path.display());
+        return Ok(None);
+      }
+      let start_instant = Instant::now();
+      let format_text_result = plugin
+        .pool
+        .format_measuring_time(|| plugin.plugin.format_text(file_path, &file_text, &config_override));
+      log_verbose!(
+        environment,
+        "Formatted file: {} in {}ms{}",
+        file_path.display(),
+        start_instant.elapsed().as_millis(),
+        if plugins_len > 1 {
+          format!(" (Plugin {}/{})", i + 1, plugins_len)
+        } else {
+          String::new()
+        },
+      );
+      file_text = Cow::Owned(format_text_result?);
+    }
+    (start_instant, file_text.into_owned())
   };
 
-  #[inline]
-  fn run_for_file_path<F, TEnvironment: Environment>(
-    environment: &TEnvironment,
-    incremental_file: &Option<Arc<IncrementalFile<TEnvironment>>>,
-    mut plugins: Vec<PluginAndPoolMutRef<TEnvironment>>,
-    file_path: &Path,
-    f: F,
-  ) -> Result<()>
-  where
-    F: Fn(&Path, &str, String, bool, Instant, &TEnvironment) -> Result<()> + Send + 'static + Clone,
+  if let Some(incremental_file) = incremental_file {
+    incremental_file.update_file(file_path, &formatted_text);
+  }
+
+  Ok(Some((file_text, FormattedFile { text: formatted_text, start_instant })))
+}
+
+/// A minimal text edit (indel) over the original file text: replace the bytes in `range` with
+/// `new_text`. An empty `new_text` is a deletion and a zero-width `range` is an insertion.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextEdit {
+  /// Byte range into the original file text that this edit replaces.
+  pub range: Range<usize>,
+  pub new_text: String,
+}
+
+/// Largest LCS table we're willing to allocate. Above this, the changed region is diffed as a
+/// single replace edit rather than line-by-line, bounding memory on pathological inputs (e.g. a
+/// large generated file that was reformatted end to end).
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// Computes the minimal set of edits that turn `original` into `formatted`.
+///
+/// The common prefix and suffix lines are trimmed first — the usual case where a formatter touches
+/// only a handful of lines in a large file reduces to a tiny diff — then the changed region is
+/// diffed line-by-line using an LCS. When that region is large enough that the LCS table would be
+/// unreasonable, it is emitted as a single replace edit instead.
+pub fn compute_text_edits(original: &str, formatted: &str) -> Vec<TextEdit> {
+  if original == formatted {
+    return Vec::new();
+  }
+
+  let old_lines = split_inclusive(original);
+  let new_lines = split_inclusive(formatted);
+
+  // trim the common prefix/suffix lines so the diff only runs over the region that changed
+  let mut prefix = 0;
+  while prefix < old_lines.len() && prefix < new_lines.len() && old_lines[prefix] == new_lines[prefix] {
+    prefix += 1;
+  }
+  let mut suffix = 0;
+  while suffix < old_lines.len() - prefix
+    && suffix < new_lines.len() - prefix
+    && old_lines[old_lines.len() - 1 - suffix] == new_lines[new_lines.len() - 1 - suffix]
   {
-    let file_text = FileText::new(environment.read_file(&file_path)?);
+    suffix += 1;
+  }
+
+  let prefix_bytes: usize = old_lines[..prefix].iter().map(|line| line.len()).sum();
+  let suffix_bytes: usize = old_lines[old_lines.len() - suffix..].iter().map(|line| line.len()).sum();
+  let old_mid = &old_lines[prefix..old_lines.len() - suffix];
+  let new_mid = &new_lines[prefix..new_lines.len() - suffix];
+
+  // bound memory: fall back to a single replace edit over the whole changed region
+  if (old_mid.len() + 1).saturating_mul(new_mid.len() + 1) > MAX_LCS_CELLS {
+    return vec![TextEdit {
+      range: prefix_bytes..original.len() - suffix_bytes,
+      new_text: new_mid.concat(),
+    }];
+  }
+
+  diff_lines(old_mid, new_mid, prefix_bytes)
+}
+
+/// Diffs two line slices with an LCS and emits the minimal indels, with byte ranges offset by
+/// `base_offset` (the byte length of the trimmed common prefix).
+fn diff_lines(old_lines: &[&str], new_lines: &[&str], base_offset: usize) -> Vec<TextEdit> {
+  // longest common subsequence of lines
+  let (m, n) = (old_lines.len(), new_lines.len());
+  let mut lengths = vec![vec![0usize; n + 1]; m + 1];
+  for i in 0..m {
+    for j in 0..n {
+      lengths[i + 1][j + 1] = if old_lines[i] == new_lines[j] {
+        lengths[i][j] + 1
+      } else {
+        lengths[i][j + 1].max(lengths[i + 1][j])
+      };
+    }
+  }
+
+  // backtrack into an ordered op sequence (Equal / Delete old / Insert new)
+  let (mut i, mut j) = (m, n);
+  let mut ops = Vec::new();
+  while i > 0 && j > 0 {
+    if old_lines[i - 1] == new_lines[j - 1] {
+      ops.push(Op::Equal(i - 1));
+      i -= 1;
+      j -= 1;
+    } else if lengths[i - 1][j] >= lengths[i][j - 1] {
+      ops.push(Op::Delete(i - 1));
+      i -= 1;
+    } else {
+      ops.push(Op::Insert(j - 1));
+      j -= 1;
+    }
+  }
+  while i > 0 {
+    ops.push(Op::Delete(i - 1));
+    i -= 1;
+  }
+  while j > 0 {
+    ops.push(Op::Insert(j - 1));
+    j -= 1;
+  }
+  ops.reverse();
 
-    if let Some(incremental_file) = incremental_file {
-      if incremental_file.is_file_same(file_path, file_text.as_str()) {
-        log_verbose!(environment, "No change: {}", file_path.display());
-        return Ok(());
+  // walk the ops in order, tracking the byte offset into the original and collapsing adjacent
+  // indels into a single replace edit
+  let mut edits = Vec::new();
+  let mut old_pos = base_offset;
+  let mut pending: Option<TextEdit> = None;
+  for op in ops {
+    match op {
+      Op::Equal(oi) => {
+        if let Some(edit) = pending.take() {
+          edits.push(edit);
+        }
+        old_pos += old_lines[oi].len();
+      }
+      Op::Delete(oi) => {
+        let line = old_lines[oi];
+        let edit = pending.get_or_insert_with(|| TextEdit { range: old_pos..old_pos, new_text: String::new() });
+        edit.range.end = old_pos + line.len();
+        old_pos += line.len();
+      }
+      Op::Insert(nj) => {
+        let edit = pending.get_or_insert_with(|| TextEdit { range: old_pos..old_pos, new_text: String::new() });
+        edit.new_text.push_str(new_lines[nj]);
       }
     }
+  }
+  if let Some(edit) = pending {
+    edits.push(edit);
+  }
 
-    let (start_instant, formatted_text) = {
-      let start_instant = Instant::now();
-      let mut file_text = Cow::Borrowed(file_text.as_str());
-      let plugins_len = plugins.len();
-      for (i, plugin) in plugins.iter_mut().enumerate() {
-        let start_instant = Instant::now();
-        let format_text_result = plugin
-          .pool
-          .format_measuring_time(|| plugin.plugin.format_text(file_path, &file_text, &ConfigKeyMap::new()));
-        log_verbose!(
-          environment,
-          "Formatted file: {} in {}ms{}",
-          file_path.display(),
-          start_instant.elapsed().as_millis(),
-          if plugins_len > 1 {
-            format!(" (Plugin {}/{})", i + 1, plugins_len)
-          } else {
-            String::new()
-          },
-        );
-        file_text = Cow::Owned(format_text_result?);
+  edits
+}
+
+/// Splits `text` into lines while keeping the trailing line terminators, so byte offsets line up
+/// exactly with the original string.
+fn split_inclusive(text: &str) -> Vec<&str> {
+  text.split_inclusive('\n').collect()
+}
+
+/// Leading directive that carries file-scoped configuration overrides.
+const CONFIG_DIRECTIVE_PREFIX: &str = "// dprint-config:";
+
+/// Extracts the file-scoped config overrides from a leading
+/// `// dprint-config: { "lineWidth": 120 }` directive. The resulting map is merged over the
+/// plugin's base configuration by `format_text`. The directive is left in place so byte offsets
+/// into the original text stay valid for edit output; an absent or malformed directive yields an
+/// empty map so formatting falls back to the base configuration.
+fn extract_config_overrides<TEnvironment: Environment>(environment: &TEnvironment, file_text: &str) -> ConfigKeyMap {
+  let first_line = file_text.lines().next().unwrap_or("").trim_start();
+  let json = match first_line.strip_prefix(CONFIG_DIRECTIVE_PREFIX) {
+    Some(json) => json.trim(),
+    None => return ConfigKeyMap::new(),
+  };
+  match parse_config_key_map(json) {
+    Ok(map) => map,
+    Err(err) => {
+      // don't silently discard the directive: tell the user it was ignored and why
+      environment.log_stderr(&format!("Ignoring invalid dprint-config directive: {}", err));
+      ConfigKeyMap::new()
+    }
+  }
+}
+
+/// Parses a JSON object of string-keyed config values into a `ConfigKeyMap`, converting each value
+/// at the boundary the same way string-keyed config values are type-converted elsewhere.
+fn parse_config_key_map(json: &str) -> Result<ConfigKeyMap> {
+  let value: serde_json::Value = serde_json::from_str(json)?;
+  let object = value.as_object().ok_or_else(|| anyhow!("Expected a JSON object in dprint-config directive."))?;
+  let mut map = ConfigKeyMap::new();
+  for (key, value) in object {
+    let config_value = match value {
+      serde_json::Value::String(text) => ConfigKeyValue::String(text.clone()),
+      serde_json::Value::Bool(value) => ConfigKeyValue::Bool(*value),
+      serde_json::Value::Number(number) => {
+        let number = number
+          .as_i64()
+          .ok_or_else(|| anyhow!("Value for '{}' must be an integer (floating point values are not supported).", key))?;
+        let number = i32::try_from(number).map_err(|_| anyhow!("Value for '{}' is out of range for a 32-bit integer.", key))?;
+        ConfigKeyValue::Number(number)
       }
-      (start_instant, file_text.into_owned())
+      _ => bail!("Unsupported config override value for key '{}'.", key),
     };
+    map.insert(key.clone(), config_value);
+  }
+  Ok(map)
+}
+
+enum Op {
+  /// The old line at this index is unchanged.
+  Equal(usize),
+  /// The old line at this index is removed.
+  Delete(usize),
+  /// The new line at this index is added.
+  Insert(usize),
+}
 
-    if let Some(incremental_file) = incremental_file {
-      incremental_file.update_file(file_path, &formatted_text);
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  /// Applies the edits back onto `original`, reconstructing what an editor would end up with.
+  fn apply(original: &str, edits: &[TextEdit]) -> String {
+    let mut result = String::new();
+    let mut pos = 0;
+    for edit in edits {
+      result.push_str(&original[pos..edit.range.start]);
+      result.push_str(&edit.new_text);
+      pos = edit.range.end;
     }
+    result.push_str(&original[pos..]);
+    result
+  }
+
+  #[test]
+  fn identical_text_has_no_edits() {
+    assert!(compute_text_edits("a\nb\n", "a\nb\n").is_empty());
+  }
+
+  #[test]
+  fn pure_insert_at_beginning() {
+    let (original, formatted) = ("b\nc\n", "a\nb\nc\n");
+    let edits = compute_text_edits(original, formatted);
+    assert_eq!(edits, vec![TextEdit { range: 0..0, new_text: "a\n".to_string() }]);
+    assert_eq!(apply(original, &edits), formatted);
+  }
+
+  #[test]
+  fn pure_insert_at_end() {
+    let (original, formatted) = ("a\nb\n", "a\nb\nc\n");
+    let edits = compute_text_edits(original, formatted);
+    assert_eq!(edits, vec![TextEdit { range: 4..4, new_text: "c\n".to_string() }]);
+    assert_eq!(apply(original, &edits), formatted);
+  }
+
+  #[test]
+  fn pure_delete() {
+    let (original, formatted) = ("a\nb\nc\n", "a\nc\n");
+    let edits = compute_text_edits(original, formatted);
+    assert_eq!(edits, vec![TextEdit { range: 2..4, new_text: String::new() }]);
+    assert_eq!(apply(original, &edits), formatted);
+  }
 
-    f(file_path, file_text.as_str(), formatted_text, file_text.has_bom(), start_instant, environment)?;
+  #[test]
+  fn replace_run() {
+    let (original, formatted) = ("a\nb\nc\nd\n", "a\nX\nY\nd\n");
+    let edits = compute_text_edits(original, formatted);
+    assert_eq!(edits, vec![TextEdit { range: 2..6, new_text: "X\nY\n".to_string() }]);
+    assert_eq!(apply(original, &edits), formatted);
+  }
+
+  #[test]
+  fn multi_byte_utf8_offsets() {
+    // "café" is 5 bytes; edits must be byte offsets that land on char boundaries
+    let (original, formatted) = ("café\nx\n", "café\ny\n");
+    let edits = compute_text_edits(original, formatted);
+    assert_eq!(edits, vec![TextEdit { range: 6..8, new_text: "y\n".to_string() }]);
+    assert_eq!(apply(original, &edits), formatted);
+  }
+
+  #[test]
+  fn no_trailing_newline_on_last_line() {
+    let (original, formatted) = ("a\nb", "a\nc");
+    let edits = compute_text_edits(original, formatted);
+    assert_eq!(edits, vec![TextEdit { range: 2..3, new_text: "c".to_string() }]);
+    assert_eq!(apply(original, &edits), formatted);
+  }
 
-    Ok(())
+  #[test]
+  fn large_all_different_region_falls_back_to_single_replace() {
+    let original = "a\n".repeat(3000);
+    let formatted = "b\n".repeat(3000);
+    let edits = compute_text_edits(&original, &formatted);
+    assert_eq!(edits.len(), 1);
+    assert_eq!(apply(&original, &edits), formatted);
   }
 }
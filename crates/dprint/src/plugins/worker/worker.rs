@@ -1,5 +1,7 @@
 use parking_lot::RwLock;
 use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use crate::environment::Environment;
@@ -18,17 +20,32 @@ pub struct StealResult<TEnvironment: Environment> {
 
 pub struct Worker<TEnvironment: Environment> {
   pub id: usize,
+  /// Shared pool-wide cancel token. Flipping it causes every worker to quiesce.
+  cancelled: Arc<AtomicBool>,
   local_work: RwLock<LocalWork<TEnvironment>>,
 }
 
 impl<TEnvironment: Environment> Worker<TEnvironment> {
-  pub fn new(id: usize, work_by_plugin: Vec<LocalPluginWork<TEnvironment>>) -> Self {
+  pub fn new(id: usize, cancelled: Arc<AtomicBool>, work_by_plugin: Vec<LocalPluginWork<TEnvironment>>) -> Self {
     Worker {
       id,
+      cancelled,
       local_work: RwLock::new(LocalWork::new(work_by_plugin)),
     }
   }
 
+  /// Returns `true` once this worker's pool has been cancelled.
+  pub fn is_cancelled(&self) -> bool {
+    self.cancelled.load(Ordering::Relaxed)
+  }
+
+  /// Flips the shared cancel token and bumps `stealer_id` so any in-flight stealers re-evaluate
+  /// who to steal from and quiesce instead of picking up more work.
+  pub fn cancel(&self) {
+    self.cancelled.store(true, Ordering::Relaxed);
+    self.local_work.write().stealer_id += 1;
+  }
+
   pub fn get_current_formatting_file_path_info(&self) -> Option<FormattingFilePathInfo> {
     self.local_work.read().get_current_formatting_file_path_info()
   }
@@ -51,6 +68,9 @@ impl<TEnvironment: Environment> Worker<TEnvironment> {
 
   pub fn try_steal(&self, steal_info: LocalWorkStealInfo) -> Option<StealResult<TEnvironment>> {
     let mut local_work = self.local_work.write();
+    if self.is_cancelled() {
+      return None; // pool was cancelled; quiesce instead of stealing more work
+    }
     if local_work.stealer_id != steal_info.stealer_id {
       return None; // someone stole before us
     }
@@ -111,6 +131,10 @@ impl<TEnvironment: Environment> Worker<TEnvironment> {
   #[allow(clippy::type_complexity)]
   pub fn take_next_work(&self) -> Option<(Arc<Vec<Arc<InitializedPluginPool<TEnvironment>>>>, PathBuf)> {
     let mut local_work = self.local_work.write();
+    if self.is_cancelled() {
+      local_work.clear_current_formatting_file_path();
+      return None;
+    }
     if let Some(work_by_plugin) = local_work.work_by_plugin.get_mut(0) {
       let pools = work_by_plugin.pools.clone();
       let file_path = work_by_plugin.take_next_work_item();